@@ -8,19 +8,41 @@ use crypto::{
 use hex::FromHex;
 use lambda_http::{lambda, IntoResponse, Request, RequestExt};
 use lambda_runtime::{error::HandlerError, Context};
-use reqwest::Client;
+use once_cell::sync::Lazy;
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
 use serde_json::json;
+use std::time::Duration;
+
+/// Reused across warm Lambda invocations so connections are kept alive.
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// A single Tokio runtime to drive the async client from the sync handler.
+static RUNTIME: Lazy<tokio::runtime::Runtime> =
+    Lazy::new(|| tokio::runtime::Runtime::new().expect("failed to start runtime"));
+
+/// Ceiling on POST attempts to a single sink before that sink counts as failed.
+const MAX_ATTEMPTS: u32 = 4;
 
 #[derive(Deserialize)]
 struct Env {
     ld_secret: String,
     dd_api_key: String,
+    /// When set, every recorded change is also emitted as a DSSE-signed audit
+    /// record signed under this secret (see [`audit`]).
+    audit_secret: Option<String>,
+    /// SQS queue URL that events land on when delivery to any sink is exhausted.
+    dead_letter_queue: Option<String>,
+    /// Enable the DogStatsD metrics sink alongside the event sink.
+    enable_metrics: Option<bool>,
+    /// Slack (or any) incoming-webhook URL to fan a formatted message out to.
+    slack_webhook_url: Option<String>,
 }
 
+/// The fields shared by every recognized LaunchDarkly resource webhook
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Payload {
+struct Resource {
     accesses: Vec<Access>,
     kind: String,
     name: String,
@@ -29,6 +51,18 @@ struct Payload {
     member: Member,
 }
 
+/// A LaunchDarkly webhook, dispatched on its `kind`
+///
+/// A recognized kind carries a fully parsed [`Resource`]; anything else is kept
+/// verbatim as [`LdEvent::Unrecognized`] so that no audit trail is lost when
+/// LaunchDarkly adds new webhook kinds. Recognized kinds all render the same
+/// way (the `kind` tag already distinguishes them), so they share one variant
+/// rather than implying per-kind behavior that does not exist.
+enum LdEvent {
+    Recognized(Resource),
+    Unrecognized(serde_json::Value),
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Member {
@@ -41,49 +75,401 @@ struct Access {
     action: String,
 }
 
+impl LdEvent {
+    /// The parsed resource behind any recognized variant, if any
+    fn resource(&self) -> Option<&Resource> {
+        match self {
+            LdEvent::Recognized(resource) => Some(resource),
+            LdEvent::Unrecognized(_) => None,
+        }
+    }
+
+    /// Dispatch a raw webhook body onto the matching variant
+    fn from_value(value: serde_json::Value) -> Self {
+        match value.get("kind").and_then(serde_json::Value::as_str) {
+            Some("flag") | Some("environment") | Some("project") | Some("member")
+            | Some("segment") => match serde_json::from_value::<Resource>(value.clone()) {
+                Ok(resource) => LdEvent::Recognized(resource),
+                Err(_) => LdEvent::Unrecognized(value),
+            },
+            _ => LdEvent::Unrecognized(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LdEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(LdEvent::from_value(serde_json::Value::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
 fn main() {
     env_logger::init();
     lambda!(handler)
 }
 
+/// A LaunchDarkly change, decoupled from any one sink's serialization
+///
+/// Every [`Sink`] renders these fields however it needs to, so the same change
+/// can fan out to an event API, a metrics counter, and a chat webhook at once.
+struct Change {
+    kind: String,
+    action: String,
+    name: String,
+    description: String,
+    /// Full name of the member who made the change (empty if unknown).
+    actor: String,
+    title_verb: String,
+    /// `false` for kinds we do not model, so sinks can fall back to a generic
+    /// rendering rather than emitting blank actor/name fields.
+    recognized: bool,
+}
+
+impl LdEvent {
+    /// Project a webhook onto the sink-agnostic [`Change`] shape
+    fn change(&self) -> Change {
+        match self.resource() {
+            Some(resource) => Change {
+                kind: resource.kind.clone(),
+                action: resource
+                    .accesses
+                    .first()
+                    .map(|access| access.action.clone())
+                    .unwrap_or_default(),
+                name: resource.name.clone(),
+                description: resource.description.clone(),
+                actor: format!(
+                    "{} {}",
+                    resource.member.first_name, resource.member.last_name
+                ),
+                title_verb: resource.title_verb.clone(),
+                recognized: true,
+            },
+            None => {
+                let value = match self {
+                    LdEvent::Unrecognized(value) => value,
+                    _ => unreachable!("resource() returned None for a recognized variant"),
+                };
+                Change {
+                    kind: value
+                        .get("kind")
+                        .and_then(serde_json::Value::as_str)
+                        .unwrap_or("unknown")
+                        .to_owned(),
+                    action: String::new(),
+                    name: String::new(),
+                    description: value
+                        .get("description")
+                        .and_then(serde_json::Value::as_str)
+                        .unwrap_or_default()
+                        .to_owned(),
+                    actor: String::new(),
+                    title_verb: String::new(),
+                    recognized: false,
+                }
+            }
+        }
+    }
+}
+
+/// A destination a [`Change`] can be routed to
+#[async_trait::async_trait]
+trait Sink {
+    /// Stable identifier recorded on the dead-letter queue so a replay only
+    /// re-delivers to the sinks that actually failed.
+    fn name(&self) -> &'static str;
+
+    async fn emit(
+        &self,
+        change: &Change,
+    ) -> Result<(), failure::Error>;
+}
+
+/// Build the set of enabled sinks from the environment
+///
+/// The Datadog event sink is always on; metrics and chat fan-out are opt-in so
+/// the same change can be routed to monitoring, alerting, and chat at once.
+fn sinks(env: &Env) -> Vec<Box<dyn Sink + Send + Sync>> {
+    let mut sinks: Vec<Box<dyn Sink + Send + Sync>> = vec![Box::new(DatadogEvents {
+        api_key: env.dd_api_key.clone(),
+    })];
+    if env.enable_metrics.unwrap_or(false) {
+        sinks.push(Box::new(DogStatsd {
+            api_key: env.dd_api_key.clone(),
+        }));
+    }
+    if let Some(url) = &env.slack_webhook_url {
+        sinks.push(Box::new(Webhook { url: url.clone() }));
+    }
+    sinks
+}
+
+/// The original sink: a Datadog event on the `/api/v1/events` API
+struct DatadogEvents {
+    api_key: String,
+}
+
 // https://docs.datadoghq.com/api/?lang=python#post-an-event
-fn event(payload: Payload) -> serde_json::Value {
+fn datadog_event(change: &Change) -> serde_json::Value {
+    if !change.recognized {
+        return json!({
+            "title": format!("LaunchDarkly {} event", change.kind),
+            "text": change.description,
+            "tags": [format!("kind:{}", change.kind)],
+            "source_type_name": "launch-darkly"
+        });
+    }
+    let mut tags = vec![
+        format!("kind:{}", change.kind),
+        format!("name:{}", change.name),
+        format!("action:{}", change.action),
+    ];
+    // A flag change is the thing most teams chart, so tag the flag explicitly.
+    if change.kind == "flag" {
+        tags.push(format!("flag:{}", change.name));
+    }
     json!({
-        "title": format!(
-            "{} {} {} {}",
-            payload.member.first_name,
-            payload.member.last_name,
-            payload.title_verb,
-            payload.name
-        ),
-         "text": payload.description,
-         "tags": [
-             format!("kind:{}", payload.kind),
-             format!("name:{}", payload.name),
-             format!("action:{}", payload.accesses[0].action)
-         ],
-         "source_type_name": "launch-darkly"
+        "title": format!("{} {} {}", change.actor, change.title_verb, change.name),
+        "text": change.description,
+        "tags": tags,
+        "source_type_name": "launch-darkly"
     })
 }
 
-/// Record webhook as Datadog event
-fn record(
-    payload: Payload,
-    dd_api_key: &str,
-) {
-    if payload.kind != "flag" {
-        return;
+#[async_trait::async_trait]
+impl Sink for DatadogEvents {
+    fn name(&self) -> &'static str {
+        "datadog-events"
     }
 
-    if let Err(err) = Client::new()
-        .post(&format!(
+    async fn emit(
+        &self,
+        change: &Change,
+    ) -> Result<(), failure::Error> {
+        let url = format!(
             "https://app.datadoghq.com/api/v1/events?api_key={}",
-            dd_api_key
-        ))
-        .json(&event(payload))
-        .send()
-    {
-        log::error!("failed to record event: {}", err)
+            self.api_key
+        );
+        post_json(&url, &datadog_event(change)).await
+    }
+}
+
+/// A DogStatsD-style counter so dashboards can chart change frequency
+///
+/// Submitted through Datadog's metrics API since a warm Lambda has no local
+/// agent to speak the UDP protocol to.
+struct DogStatsd {
+    api_key: String,
+}
+
+#[async_trait::async_trait]
+impl Sink for DogStatsd {
+    fn name(&self) -> &'static str {
+        "dogstatsd"
+    }
+
+    async fn emit(
+        &self,
+        change: &Change,
+    ) -> Result<(), failure::Error> {
+        let url = format!(
+            "https://app.datadoghq.com/api/v1/series?api_key={}",
+            self.api_key
+        );
+        let mut tags = vec![format!("kind:{}", change.kind)];
+        if !change.action.is_empty() {
+            tags.push(format!("action:{}", change.action));
+        }
+        // Only a flag change has a meaningful `flag:` tag; other kinds and
+        // unrecognized events would otherwise emit a bogus or empty one.
+        if change.kind == "flag" {
+            tags.push(format!("flag:{}", change.name));
+        }
+        let series = json!({
+            "series": [{
+                "metric": "launchdarkly.change",
+                "type": "count",
+                "points": [[now_secs(), 1]],
+                "tags": tags
+            }]
+        });
+        post_json(&url, &series).await
+    }
+}
+
+/// A Slack/generic webhook that fans out a human-readable message
+struct Webhook {
+    url: String,
+}
+
+#[async_trait::async_trait]
+impl Sink for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn emit(
+        &self,
+        change: &Change,
+    ) -> Result<(), failure::Error> {
+        let text = if change.recognized {
+            format!("{} {} {}", change.actor, change.title_verb, change.name)
+        } else {
+            format!("LaunchDarkly {} event", change.kind)
+        };
+        post_json(&self.url, &json!({ "text": text })).await
+    }
+}
+
+/// Seconds since the Unix epoch, for metric timestamps.
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Outcome of attempting to record a change
+enum Delivery {
+    /// Every enabled sink accepted the event.
+    Recorded,
+    /// At least one sink failed but the event was parked on the dead-letter
+    /// queue for later replay.
+    Deferred,
+    /// At least one sink failed and the event could not be dead-lettered (no
+    /// queue configured, or the send to it failed); those sinks never get it.
+    Dropped,
+}
+
+/// Record a webhook by fanning it out to every enabled sink, retrying
+/// transient failures and dead-lettering anything that cannot be delivered
+fn record(
+    payload: LdEvent,
+    body: &[u8],
+    env: &Env,
+) -> Delivery {
+    let change = payload.change();
+    let sinks = sinks(env);
+    RUNTIME.block_on(async {
+        let mut failed = Vec::new();
+        for sink in &sinks {
+            if let Err(err) = sink.emit(&change).await {
+                log::error!("{} sink failed: {}", sink.name(), err);
+                failed.push(sink.name());
+            }
+        }
+        if failed.is_empty() {
+            return Delivery::Recorded;
+        }
+        match env.dead_letter_queue.as_deref() {
+            Some(queue) => {
+                // Park only the sinks that failed so a replay does not
+                // re-deliver to sinks that already accepted the change.
+                let record = json!({
+                    "body": String::from_utf8_lossy(body),
+                    "event": datadog_event(&change),
+                    "sinks": failed,
+                });
+                if dead_letter(queue, &record).await {
+                    Delivery::Deferred
+                } else {
+                    Delivery::Dropped
+                }
+            }
+            None => Delivery::Dropped,
+        }
+    })
+}
+
+/// POST a JSON body, retrying `429`/`5xx` with exponential backoff
+async fn post_json(
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<(), failure::Error> {
+    let mut backoff = Duration::from_millis(200);
+    for attempt in 1..=MAX_ATTEMPTS {
+        match CLIENT.post(url).json(body).send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) if is_retryable(response.status()) => {
+                // Nothing follows the last attempt, so don't burn the
+                // `Retry-After`/backoff delay before giving up.
+                if attempt == MAX_ATTEMPTS {
+                    break;
+                }
+                let wait = retry_after(&response).unwrap_or(backoff);
+                log::warn!(
+                    "sink returned {} (attempt {}/{}), retrying in {:?}",
+                    response.status(),
+                    attempt,
+                    MAX_ATTEMPTS,
+                    wait
+                );
+                tokio::time::sleep(wait).await;
+                backoff *= 2;
+            }
+            Ok(response) => {
+                return Err(failure::err_msg(format!(
+                    "sink rejected request: {}",
+                    response.status()
+                )));
+            }
+            Err(err) => {
+                if attempt == MAX_ATTEMPTS {
+                    return Err(failure::err_msg(err.to_string()));
+                }
+                log::warn!(
+                    "failed to reach sink (attempt {}/{}): {}",
+                    attempt,
+                    MAX_ATTEMPTS,
+                    err
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+    Err(failure::err_msg("exhausted retries"))
+}
+
+/// `429` and `5xx` are worth retrying; other statuses are terminal.
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Honor Datadog's `Retry-After` (seconds) on a `429` when present.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Park an undeliverable event on the configured SQS dead-letter queue
+async fn dead_letter(
+    queue_url: &str,
+    record: &serde_json::Value,
+) -> bool {
+    use rusoto_sqs::{SendMessageRequest, Sqs, SqsClient};
+
+    let client = SqsClient::new(rusoto_core::Region::default());
+    let request = SendMessageRequest {
+        queue_url: queue_url.to_owned(),
+        message_body: record.to_string(),
+        ..Default::default()
+    };
+    match client.send_message(request).await {
+        Ok(_) => true,
+        Err(err) => {
+            log::error!("failed to dead-letter event: {}", err);
+            false
+        }
     }
 }
 
@@ -91,22 +477,31 @@ fn handler(
     request: Request,
     _: Context,
 ) -> Result<impl IntoResponse, HandlerError> {
-    let Env {
-        ld_secret,
-        dd_api_key,
-    } = envy::from_env::<Env>().map_err(|e| failure::err_msg(e.to_string()))?;
+    let env = envy::from_env::<Env>().map_err(|e| failure::err_msg(e.to_string()))?;
 
-    if !authenticated(&request, &ld_secret) {
+    if !authenticated(&request, &env.ld_secret) {
         log::warn!("request was not authenticated");
         return Ok(json!({
             "message": "Request not authenticated"
         }));
     }
 
-    if let Ok(Some(payload)) = request.payload::<Payload>() {
-        record(payload, &dd_api_key);
+    // The raw, verified bytes the audit record binds to.
+    let body = request.body().to_vec();
+
+    if let Ok(Some(payload)) = request.payload::<LdEvent>() {
+        let envelope = env
+            .audit_secret
+            .as_ref()
+            .map(|secret| audit::envelope(&statement(&payload, &body), secret));
+        let message = match record(payload, &body, &env) {
+            Delivery::Recorded => "👍",
+            Delivery::Deferred => "Accepted; delivery deferred to dead-letter queue",
+            Delivery::Dropped => "Accepted, but delivery failed and was dropped",
+        };
         return Ok(json!({
-            "message": "👍"
+            "message": message,
+            "audit": envelope
         }));
     }
 
@@ -117,40 +512,184 @@ fn handler(
 
 /// Verifies a request was triggered by ld
 ///
+/// `secrets` is a comma-separated list so a rotation can run with both the old
+/// and new signing secret configured: the request is accepted if it verifies
+/// against any of them. The comparison stays constant-time via [`MacResult`],
+/// and a missing or malformed `X-LD-Signature` is rejected outright.
+///
 /// see [these docs](https://docs.launchdarkly.com/docs/webhooks#section-signing-webhooks) for
 /// further reference
 fn authenticated(
     request: &Request,
-    secret: &str,
+    secrets: &str,
 ) -> bool {
-    request
+    let signature = match request
         .headers()
         .get("X-LD-Signature")
         .and_then(|value| Vec::from_hex(value).ok())
-        .iter()
-        .any(|signature| {
-            let mut mac = Hmac::new(Sha256::new(), &secret.as_bytes());
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    secrets
+        .split(',')
+        .map(str::trim)
+        .filter(|secret| !secret.is_empty())
+        .any(|secret| {
+            let mut mac = Hmac::new(Sha256::new(), secret.as_bytes());
             mac.input(&request.body());
             mac.result() == MacResult::new(&signature)
         })
 }
 
+/// Describe a single flag change as an in-toto statement
+///
+/// The statement binds the change to the exact bytes that passed
+/// [`authenticated()`] via their SHA-256 hash, so a verifier can prove the
+/// signed record covers the request LaunchDarkly actually sent.
+fn statement(
+    payload: &LdEvent,
+    body: &[u8],
+) -> serde_json::Value {
+    let (member, action, flag) = match payload.resource() {
+        Some(resource) => (
+            format!(
+                "{} {}",
+                resource.member.first_name, resource.member.last_name
+            ),
+            resource
+                .accesses
+                .first()
+                .map(|access| access.action.clone())
+                .unwrap_or_default(),
+            resource.name.clone(),
+        ),
+        None => (String::new(), String::new(), String::new()),
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    json!({
+        "_type": "https://in-toto.io/Statement/v0.1",
+        "member": member,
+        "action": action,
+        "flag": flag,
+        "timestamp": timestamp,
+        "bodySha256": audit::body_sha256(body),
+    })
+}
+
+/// DSSE (Dead Simple Signing Envelope) signing of audit statements
+///
+/// A first cut signs the Pre-Auth Encoding with HMAC-SHA256; the envelope shape
+/// leaves room to swap in ECDSA P-256 later without touching callers.
+mod audit {
+    use crypto::{digest::Digest, hmac::Hmac, mac::Mac, mac::MacResult, sha2::Sha256};
+    use serde_json::json;
+
+    const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+    const KEY_ID: &str = "hmac-sha256";
+
+    /// Sign a statement and serialize it as a DSSE envelope
+    pub fn envelope(
+        statement: &serde_json::Value,
+        secret: &str,
+    ) -> serde_json::Value {
+        let payload = base64::encode(serde_json::to_vec(statement).unwrap_or_default());
+        let signature = sign(&pae(payload.as_bytes()), secret);
+        json!({
+            "payloadType": PAYLOAD_TYPE,
+            "payload": payload,
+            "signatures": [{ "keyid": KEY_ID, "sig": base64::encode(signature) }]
+        })
+    }
+
+    /// Recompute the PAE and confirm the envelope's signature under `secret`
+    pub fn verify(
+        envelope: &serde_json::Value,
+        secret: &str,
+    ) -> bool {
+        let payload = match envelope.get("payload").and_then(serde_json::Value::as_str) {
+            Some(payload) => payload,
+            None => return false,
+        };
+        let signature = match envelope
+            .pointer("/signatures/0/sig")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|sig| base64::decode(sig).ok())
+        {
+            Some(signature) => signature,
+            None => return false,
+        };
+        let expected = sign(&pae(payload.as_bytes()), secret);
+        MacResult::new(&expected) == MacResult::new(&signature)
+    }
+
+    /// `"DSSEv1" SP len(payloadType) SP payloadType SP len(payload) SP payload`
+    fn pae(payload: &[u8]) -> Vec<u8> {
+        let mut pae = Vec::new();
+        pae.extend_from_slice(b"DSSEv1 ");
+        pae.extend_from_slice(PAYLOAD_TYPE.len().to_string().as_bytes());
+        pae.push(b' ');
+        pae.extend_from_slice(PAYLOAD_TYPE.as_bytes());
+        pae.push(b' ');
+        pae.extend_from_slice(payload.len().to_string().as_bytes());
+        pae.push(b' ');
+        pae.extend_from_slice(payload);
+        pae
+    }
+
+    fn sign(
+        pae: &[u8],
+        secret: &str,
+    ) -> Vec<u8> {
+        let mut mac = Hmac::new(Sha256::new(), secret.as_bytes());
+        mac.input(pae);
+        mac.result().code().to_vec()
+    }
+
+    /// Hex-encoded SHA-256 of the raw, HMAC-verified request body
+    pub fn body_sha256(body: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.input(body);
+        hasher.result_str()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn payload_parses() {
-        serde_json::from_str::<Payload>(include_str!("../tests/data/payload.json"))
+        let payload = serde_json::from_str::<LdEvent>(include_str!("../tests/data/payload.json"))
             .expect("failed to parse payload");
+        assert!(matches!(payload, LdEvent::Recognized(_)));
+    }
+
+    #[test]
+    fn unrecognized_kind_is_kept() {
+        let payload = serde_json::from_str::<LdEvent>(r#"{"kind":"goal","description":"x"}"#)
+            .expect("failed to parse payload");
+        assert!(matches!(payload, LdEvent::Unrecognized(_)));
+        assert_eq!(
+            datadog_event(&payload.change()),
+            json!({
+                "title": "LaunchDarkly goal event",
+                "text": "x",
+                "tags": ["kind:goal"],
+                "source_type_name": "launch-darkly"
+            })
+        );
     }
 
     #[test]
     fn creates_event() {
-        let payload = serde_json::from_str::<Payload>(include_str!("../tests/data/payload.json"))
+        let payload = serde_json::from_str::<LdEvent>(include_str!("../tests/data/payload.json"))
             .expect("failed to parse payload");
         assert_eq!(
-            event(payload),
+            datadog_event(&payload.change()),
             json!({
                 "title": "Reese Applebaum changed the name of Testing",
                 "text": "- Changed the name from ~~Test~~ to *Testing*",
@@ -175,4 +714,41 @@ mod tests {
             .expect("failed to generate request");
         assert!(authenticated(&request, "secret"))
     }
+
+    #[test]
+    fn authenticates_against_any_rotated_secret() {
+        let body = include_str!("../tests/data/payload.json");
+
+        let mut mac = Hmac::new(Sha256::new(), b"new");
+        mac.input(body.as_bytes());
+        let signature = hex::encode(mac.result().code());
+
+        let request = http::Request::builder()
+            .header("X-LD-Signature", signature)
+            .body(body.into())
+            .expect("failed to generate request");
+        // Signed with "new"; "old" is still configured mid-rotation.
+        assert!(authenticated(&request, "old,new"));
+        assert!(!authenticated(&request, "old,other"));
+    }
+
+    #[test]
+    fn rejects_missing_signature() {
+        let request = http::Request::builder()
+            .body("{}".into())
+            .expect("failed to generate request");
+        assert!(!authenticated(&request, "secret"));
+    }
+
+    #[test]
+    fn audit_envelope_round_trips() {
+        let body = include_str!("../tests/data/payload.json");
+        let payload =
+            serde_json::from_str::<LdEvent>(body).expect("failed to parse payload");
+        let envelope = audit::envelope(&statement(&payload, body.as_bytes()), "audit-secret");
+
+        assert_eq!(envelope["payloadType"], "application/vnd.in-toto+json");
+        assert!(audit::verify(&envelope, "audit-secret"));
+        assert!(!audit::verify(&envelope, "wrong-secret"));
+    }
 }